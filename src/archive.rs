@@ -0,0 +1,335 @@
+//! Archive-format adapters for expanding nested student submissions.
+//!
+//! Blackboard submissions are most often `.zip` files, but students also submit `.tar`, `.tar.gz`/`.tgz`, and plain
+//! `.gz` archives. This module generalizes what used to be a hard-coded `== "zip"` check in `process_submission`
+//! into a small adapter registry, modeled loosely on ripgrep-all's `FileAdapter` trait: each format knows its own
+//! extensions and how to expand itself into a destination directory, and [`extract_recursive`] dispatches on a
+//! file's extension and keeps re-scanning the extracted output for further archives (bounded by `max_depth`), so
+//! that e.g. a `.tar.gz` containing a `.zip` still gets fully unpacked.
+//!
+//! Zip entries may also be password-protected; an optional password is threaded down to [`ZipAdapter`] (the only
+//! format here that supports encryption), and [`is_password_error`] lets callers recognize a missing/incorrect
+//! password so they can fall back to writing the raw archive instead of aborting the whole submission.
+//!
+//! [`verify_zip`] gives callers a way to catch a truncated/corrupt zip submission up front, before extracting it.
+
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use thiserror::Error;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+/// Default recursion limit for nested archives, to guard against zip-bomb-style infinite nesting.
+pub const DEFAULT_MAX_DEPTH: u32 = 3;
+
+/// The folder prefix used for a submission file that got expanded into a directory.
+const EXTRACTED_PREFIX: &str = "[Extracted] ";
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("could not read/write archive contents: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("could not decompress zip archive: {0}")]
+    Zip(#[from] ZipError),
+}
+
+/// A format that can be expanded into a directory.
+///
+/// Implementors are registered in [`registry`] and dispatched on by [`adapter_for`]. `Sync` is required so that
+/// the registry can be a `static` instead of being rebuilt on every lookup.
+pub trait ArchiveAdapter: Sync {
+    /// File extensions (without a leading dot) that this adapter handles. Matched case-insensitively against the
+    /// end of a filename; adapters may list multi-part extensions like `"tar.gz"`.
+    fn extensions(&self) -> &[&str];
+
+    /// Expand `bytes` into `dest`, which does not yet exist. `password` is only meaningful to [`ZipAdapter`]; other
+    /// formats ignore it.
+    fn extract(&self, bytes: &[u8], dest: &Path, password: Option<&[u8]>) -> Result<(), ArchiveError>;
+}
+
+/// True if `err` indicates that a zip entry is encrypted and couldn't be read, either because no password was
+/// supplied or because the one supplied was wrong. `UnsupportedArchive` also covers unrelated cases (an unsupported
+/// compression method, zip64, etc.), so its message has to actually mention a password before it counts.
+pub fn is_password_error(err: &ZipError) -> bool {
+    match err {
+        ZipError::UnsupportedArchive(msg) => msg.to_lowercase().contains("password"),
+        _ => err.to_string().to_lowercase().contains("password"),
+    }
+}
+
+/// True if `name` names a plain `.zip` file, i.e. the only format here with a per-entry CRC-32 that
+/// [`verify_zip`] can check.
+pub fn is_zip(name: &str) -> bool {
+    name.to_lowercase().ends_with(".zip")
+}
+
+/// Reads every entry of a zip archive all the way through, discarding the contents, to force the CRC-32 check
+/// that the `zip` crate performs as each entry's decompressed stream reaches its end. This catches a truncated or
+/// otherwise corrupt submission before anything gets written to disk, rather than leaving a half-extracted folder
+/// behind.
+pub fn verify_zip(bytes: &[u8], password: Option<&[u8]>) -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+    for i in 0..archive.len() {
+        let mut file = match password {
+            Some(password) => archive.by_index_decrypt(i, password)?,
+            None => archive.by_index(i)?,
+        };
+        io::copy(&mut file, &mut io::sink())?;
+    }
+    Ok(())
+}
+
+struct ZipAdapter;
+
+impl ArchiveAdapter for ZipAdapter {
+    fn extensions(&self) -> &[&str] {
+        &["zip"]
+    }
+
+    fn extract(&self, bytes: &[u8], dest: &Path, password: Option<&[u8]>) -> Result<(), ArchiveError> {
+        let cursor = Cursor::new(bytes);
+        let mut archive = ZipArchive::new(cursor)?;
+        match password {
+            Some(password) => extract_zip_with_password(&mut archive, dest, password)?,
+            None => archive.extract(dest)?,
+        }
+        Ok(())
+    }
+}
+
+/// `ZipArchive` has no password-aware equivalent of `extract()`, so this decrypts and writes out each entry by
+/// hand, the same way `extract()` does internally for an unencrypted archive.
+fn extract_zip_with_password(
+    archive: &mut ZipArchive<Cursor<&[u8]>>,
+    dest: &Path,
+    password: &[u8],
+) -> Result<(), ArchiveError> {
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index_decrypt(i, password)?;
+
+        // Skip entries whose name can't be safely joined onto `dest` (absolute paths, `..` components, etc.),
+        // same as `extract()` does for an unencrypted archive.
+        let Some(relative) = entry.enclosed_name() else {
+            continue;
+        };
+        let out_path = dest.join(relative);
+
+        if entry.is_dir() {
+            fs::create_dir_all(&out_path)?;
+            continue;
+        }
+
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut out = File::create(&out_path)?;
+        io::copy(&mut entry, &mut out)?;
+    }
+    Ok(())
+}
+
+struct TarAdapter;
+
+impl ArchiveAdapter for TarAdapter {
+    fn extensions(&self) -> &[&str] {
+        &["tar"]
+    }
+
+    fn extract(&self, bytes: &[u8], dest: &Path, _password: Option<&[u8]>) -> Result<(), ArchiveError> {
+        let cursor = Cursor::new(bytes);
+        tar::Archive::new(cursor).unpack(dest)?;
+        Ok(())
+    }
+}
+
+struct TarGzAdapter;
+
+impl ArchiveAdapter for TarGzAdapter {
+    fn extensions(&self) -> &[&str] {
+        &["tar.gz", "tgz"]
+    }
+
+    fn extract(&self, bytes: &[u8], dest: &Path, _password: Option<&[u8]>) -> Result<(), ArchiveError> {
+        let cursor = Cursor::new(bytes);
+        tar::Archive::new(GzDecoder::new(cursor)).unpack(dest)?;
+        Ok(())
+    }
+}
+
+struct GzAdapter;
+
+impl ArchiveAdapter for GzAdapter {
+    fn extensions(&self) -> &[&str] {
+        &["gz"]
+    }
+
+    fn extract(&self, bytes: &[u8], dest: &Path, _password: Option<&[u8]>) -> Result<(), ArchiveError> {
+        // A lone `.gz` wraps a single file rather than a directory tree; unpack it as `dest/<original name>`. `dest`
+        // is always named `EXTRACTED_PREFIX` + the original file's stem (see `extracted_dir_name`), so recover the
+        // inner filename from there instead of inventing a new one.
+        fs::create_dir_all(dest)?;
+        let inner_name = dest
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix(EXTRACTED_PREFIX))
+            .unwrap_or("contents");
+
+        let cursor = Cursor::new(bytes);
+        let mut reader = GzDecoder::new(cursor);
+        let mut out = File::create(dest.join(inner_name))?;
+        io::copy(&mut reader, &mut out)?;
+        Ok(())
+    }
+}
+
+/// All known adapters, checked in order by [`adapter_for`].
+fn registry() -> &'static [&'static dyn ArchiveAdapter] {
+    static REGISTRY: [&dyn ArchiveAdapter; 4] = [&ZipAdapter, &TarAdapter, &TarGzAdapter, &GzAdapter];
+    &REGISTRY
+}
+
+/// Finds the adapter that should handle `name`, preferring the longest matching extension so that e.g. `tar.gz`
+/// wins out over the plain `gz` adapter for a file named `foo.tar.gz`.
+pub fn adapter_for(name: &str) -> Option<&'static dyn ArchiveAdapter> {
+    let lower = name.to_lowercase();
+    registry()
+        .iter()
+        .flat_map(|adapter| adapter.extensions().iter().map(move |ext| (*ext, *adapter)))
+        .filter(|(ext, _)| lower.ends_with(&format!(".{ext}")))
+        .max_by_key(|(ext, _)| ext.len())
+        .map(|(_, adapter)| adapter)
+}
+
+/// The directory name that a submitted archive file should be expanded into, e.g. `"report.tar.gz"` becomes
+/// `"[Extracted] report"`.
+pub fn extracted_dir_name(original_name: &Path) -> OsString {
+    let file_name = original_name.file_name().unwrap_or_default().to_string_lossy();
+    let lower = file_name.to_lowercase();
+
+    let stem = registry()
+        .iter()
+        .flat_map(|adapter| adapter.extensions())
+        .filter(|ext| lower.ends_with(&format!(".{ext}")))
+        .max_by_key(|ext| ext.len())
+        .map(|ext| &file_name[..file_name.len() - ext.len() - 1])
+        .unwrap_or(&file_name);
+
+    let mut folder_name = OsString::from(EXTRACTED_PREFIX);
+    folder_name.push(stem);
+    folder_name
+}
+
+/// A nested archive found during [`rescan_for_nested`] that failed its pre-extraction CRC check and was left in
+/// place, un-expanded, instead of being extracted. Carried back up to the caller (ultimately `process_submission`)
+/// so it can be reported the same way a corrupt top-level submission is: as a non-fatal warning.
+pub struct CorruptNested {
+    pub name: String,
+    pub detail: ArchiveError,
+}
+
+/// A nested zip found during [`rescan_for_nested`] that turned out to be password-protected and was left in place,
+/// un-expanded, instead of being extracted. Reported the same way a password-protected top-level submission is: as
+/// a non-fatal warning naming the nested file itself, not whatever archive it was found inside of.
+pub struct EncryptedNested {
+    pub name: String,
+    pub detail: ZipError,
+}
+
+/// Problems found while recursively expanding nested archives, accumulated rather than aborting the whole
+/// submission: each one is left un-extracted in place and reported back as a warning instead.
+#[derive(Default)]
+pub struct NestedIssues {
+    pub corrupt: Vec<CorruptNested>,
+    pub encrypted: Vec<EncryptedNested>,
+}
+
+impl NestedIssues {
+    fn extend(&mut self, other: NestedIssues) {
+        self.corrupt.extend(other.corrupt);
+        self.encrypted.extend(other.encrypted);
+    }
+}
+
+/// Extracts `bytes` (a file named like `original_name`) into `dest` using `adapter`, then recursively re-scans the
+/// result for further archives, up to `max_depth` levels of nesting, so that e.g. a `.tar.gz` containing a `.zip`
+/// ends up fully expanded rather than leaving a nested archive file sitting in the output. Returns any nested zips
+/// found along the way that were corrupt or password-protected.
+pub fn extract_recursive(
+    adapter: &dyn ArchiveAdapter,
+    bytes: &[u8],
+    dest: &Path,
+    password: Option<&[u8]>,
+    max_depth: u32,
+) -> Result<NestedIssues, ArchiveError> {
+    adapter.extract(bytes, dest, password)?;
+
+    if max_depth > 0 {
+        rescan_for_nested(dest, password, max_depth - 1)
+    } else {
+        Ok(NestedIssues::default())
+    }
+}
+
+/// Walks `dir` looking for files whose extension matches a known archive format, and expands them in place. Zips
+/// are CRC-checked before extraction, same as a top-level submission: a corrupt or password-protected one is left
+/// in place and reported back as a [`NestedIssues`] entry instead of aborting the whole submission.
+fn rescan_for_nested(dir: &Path, password: Option<&[u8]>, remaining_depth: u32) -> Result<NestedIssues, ArchiveError> {
+    let mut issues = NestedIssues::default();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            issues.extend(rescan_for_nested(&path, password, remaining_depth)?);
+            continue;
+        }
+
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        let Some(adapter) = adapter_for(&name) else {
+            continue;
+        };
+        if remaining_depth == 0 {
+            // Leave further nested archives un-expanded rather than recursing past the configured depth.
+            continue;
+        }
+
+        let bytes = fs::read(&path)?;
+
+        if is_zip(&name) {
+            match verify_zip(&bytes, password) {
+                Ok(()) => (),
+                Err(ArchiveError::Zip(e)) if is_password_error(&e) => (), // let `extract` below handle/report it
+                Err(detail) => {
+                    issues.corrupt.push(CorruptNested { name, detail });
+                    continue;
+                },
+            }
+        }
+
+        // Don't touch `path` until we know extraction actually worked: if it fails partway through (most often
+        // because it's password-protected), this leaves the nested archive exactly as submitted instead of losing
+        // it, and doesn't disturb any sibling files that already got extracted successfully.
+        let nested_dest: PathBuf = path.with_file_name(extracted_dir_name(&path));
+
+        match extract_recursive(adapter, &bytes, &nested_dest, password, remaining_depth) {
+            Ok(sub_issues) => {
+                fs::remove_file(&path)?;
+                issues.extend(sub_issues);
+            },
+            Err(ArchiveError::Zip(e)) if is_password_error(&e) => {
+                let _ = fs::remove_dir_all(&nested_dest);
+                issues.encrypted.push(EncryptedNested { name, detail: e });
+            },
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(issues)
+}
@@ -1,8 +1,7 @@
 use std::cmp::Ordering;
-use std::collections::HashMap;
-use std::ffi::OsString;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
-use std::io::{Cursor, Read, Seek};
+use std::io::{BufReader, Read, Seek, Write};
 use std::iter::Peekable;
 use std::path::{Path, PathBuf};
 use std::process::ExitCode;
@@ -11,11 +10,17 @@ use std::sync::LazyLock;
 use std::{env, io};
 
 use chrono::NaiveDateTime;
+use rayon::prelude::*;
 use regex::Regex;
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 use zip::result::ZipError;
 use zip::ZipArchive;
 
+mod archive;
+
+use archive::{ArchiveError, CorruptNested, EncryptedNested};
+
 // cspell:words gradebook fullname
 // cspell:words firstnamelastname firstlast
 
@@ -27,7 +32,8 @@ working directory.
 
 Usage:
 
-    $ bb-gradebook <gradebook> [output_dir] [--full-names|-n]
+    $ bb-gradebook <gradebook> [output_dir] [--full-names|-n] [--password <pw>|--password-file <path>] [-j N]
+    $ bb-gradebook --list <gradebook>
 
 Parameters:
 
@@ -40,6 +46,33 @@ Parameters:
     -n
     --full-names    Use students' full names for folders, instead of their
                     shortened 'firstnamelastname' usernames.
+
+    --password <pw>
+    --password-file <path>
+                    A password to try against any encrypted zip files that
+                    students submitted. A submitted zip that still can't be
+                    opened with it is left on disk undecrypted, with a
+                    warning, instead of failing the whole submission.
+
+    -j, --jobs N    How many submissions to extract in parallel. Defaults to
+                    the number of available CPU cores.
+
+    --verify        Check every submitted file's integrity before writing it
+                    out: nested zips are always CRC-checked, and with this
+                    flag a plain file that's corrupt/truncated in the
+                    gradebook itself is also left on disk as-is with a
+                    warning, instead of failing the whole submission.
+
+    --list          Don't extract anything; instead print a summary of each
+                    student's submissions (attempts, timestamps, whether
+                    there's a text submission/comments, and submitted files
+                    with their sizes) so you can sanity-check a gradebook
+                    before writing hundreds of folders to disk.
+
+    --manifest      Write a '[Blackboard] Manifest.txt' into every extracted
+                    submission folder, recording each file's original name,
+                    size, and SHA-256 digest, then print any digest shared by
+                    more than one student as a first-pass copy-detection hint.
 ";
 
 const SUBMISSION_DATE_FORMAT: &'static str = "%A, %B %-d, %Y %-I:%M:%S %p %Z";
@@ -83,11 +116,11 @@ enum SubmissionError {
         detail: io::Error,
     },
 
-    #[error("failed to unzip a submitted zip for {student} (attempt {attempt}):\n{detail}")]
+    #[error("failed to extract a nested archive for {student} (attempt {attempt}):\n{detail}")]
     Extract {
         student: String,
         attempt: NaiveDateTime,
-        detail: ZipError,
+        detail: ArchiveError,
     },
 }
 
@@ -98,13 +131,54 @@ impl SubmissionError {
         Self::IOWrite { student, attempt, detail }
     }
 
-    pub fn from_zip(submission: &Submission, detail: ZipError) -> Self {
+    pub fn from_archive(submission: &Submission, detail: ArchiveError) -> Self {
         let student = submission.fullname.to_string();
         let attempt = submission.datetime;
         Self::Extract { student, attempt, detail }
     }
 }
 
+/// A non-fatal problem encountered while processing a submission. Unlike [`SubmissionError`], these don't stop the
+/// submission from being written out; they're collected and printed alongside the final summary.
+#[derive(Debug, Error)]
+enum SubmissionWarning {
+    #[error(
+        "could not decrypt a password-protected zip for {student} (attempt {attempt}), '{file}' was left on disk \
+         undecrypted:\n{detail}"
+    )]
+    EncryptedArchive {
+        student: String,
+        attempt: NaiveDateTime,
+        file: String,
+        detail: ZipError,
+    },
+
+    #[error("corrupt/truncated file for {student} (attempt {attempt}), '{file}' was left on disk as-is:\n{detail}")]
+    CorruptArchive {
+        student: String,
+        attempt: NaiveDateTime,
+        file: String,
+        detail: String,
+    },
+}
+
+impl SubmissionWarning {
+    pub fn encrypted_archive(submission: &Submission, file: &str, detail: ZipError) -> Self {
+        let student = submission.fullname.to_string();
+        let attempt = submission.datetime;
+        let file = file.to_string();
+        Self::EncryptedArchive { student, attempt, file, detail }
+    }
+
+    pub fn corrupt_archive(submission: &Submission, file: &str, detail: impl std::fmt::Display) -> Self {
+        let student = submission.fullname.to_string();
+        let attempt = submission.datetime;
+        let file = file.to_string();
+        let detail = detail.to_string();
+        Self::CorruptArchive { student, attempt, file, detail }
+    }
+}
+
 
 fn main() -> ExitCode {
     // Get the input filename from arguments
@@ -113,19 +187,66 @@ fn main() -> ExitCode {
     let args = env::args().skip(1).collect::<Vec<_>>();
 
     let mut use_full_names = false;
-    for arg in &args {
+    let mut password: Option<Vec<u8>> = None;
+    let mut jobs: Option<usize> = None;
+    let mut list_mode = false;
+    let mut verify = false;
+    let mut manifest = false;
+    let mut pos_args = Vec::new();
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
         match &arg[..] {
             "-n" | "--full-names" => use_full_names = true,
+            "--list" => list_mode = true,
+            "--verify" => verify = true,
+            "--manifest" => manifest = true,
             "-h" | "--help" => {
                 println!("{HELP_STR}");
                 return ExitCode::SUCCESS;
             },
-            _ => (),
+            "--password" => {
+                let Some(pw) = args_iter.next() else {
+                    eprintln!("--password requires a value.");
+                    return ExitCode::FAILURE;
+                };
+                password = Some(pw.as_bytes().to_vec());
+            },
+            "--password-file" => {
+                let Some(path) = args_iter.next() else {
+                    eprintln!("--password-file requires a value.");
+                    return ExitCode::FAILURE;
+                };
+                match fs::read_to_string(path) {
+                    Ok(contents) => password = Some(contents.trim_end_matches(['\r', '\n']).as_bytes().to_vec()),
+                    Err(err) => {
+                        eprintln!("Could not read password file '{path}': {err}");
+                        return ExitCode::FAILURE;
+                    },
+                }
+            },
+            "-j" | "--jobs" => {
+                let Some(n) = args_iter.next() else {
+                    eprintln!("--jobs requires a value.");
+                    return ExitCode::FAILURE;
+                };
+                let Ok(n) = n.parse::<usize>() else {
+                    eprintln!("--jobs expects a positive number, got '{n}'.");
+                    return ExitCode::FAILURE;
+                };
+                if n == 0 {
+                    eprintln!("--jobs expects a positive number, got '{n}'.");
+                    return ExitCode::FAILURE;
+                }
+                jobs = Some(n);
+            },
+            // Filter out non-positional arguments
+            arg if arg.starts_with("-") => (),
+            _ => pos_args.push(arg),
         }
     }
 
-    // Filter out non-positional arguments
-    let mut pos_args = args.iter().filter(|arg| !arg.starts_with("-"));
+    let mut pos_args = pos_args.into_iter();
 
     let Some(archive_path) = pos_args.next() else {
         eprintln!("Please provide a 'gradebook' zip downloaded from Blackboard to unzip.");
@@ -134,17 +255,42 @@ fn main() -> ExitCode {
 
     let out_dir = pos_args.next().map(|string| &string[..]); // borrow owned string from arguments.
 
+    if list_mode {
+        return match list_gradebook(archive_path) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprint!("{}", err);
+                ExitCode::FAILURE
+            },
+        };
+    }
+
     // Process the entire gradebook, erroring
-    match process_gradebook(&archive_path, out_dir, use_full_names) {
+    match process_gradebook(archive_path, out_dir, use_full_names, password.as_deref(), jobs, verify, manifest) {
         Ok(results) => {
             let n_total = results.len();
-            let errors = results.into_iter().filter_map(|r| r.err()).collect::<Vec<_>>();
+
+            let mut warnings = Vec::new();
+            let mut errors = Vec::new();
+            for result in results {
+                match result {
+                    Ok(sub_warnings) => warnings.extend(sub_warnings),
+                    Err(err) => errors.push(err),
+                }
+            }
 
             let n_err = errors.len();
             let n_ok = n_total - n_err;
 
             println!("\nSuccessfully extracted {n_ok} submissions.");
 
+            if !warnings.is_empty() {
+                eprintln!("Encountered {} non-fatal warnings:", warnings.len());
+                for warning in &warnings {
+                    eprintln!("\t{warning}");
+                }
+            }
+
             if n_err > 0 {
                 eprintln!("Encountered {n_err} errors when extracting:");
                 for error in errors {
@@ -164,30 +310,68 @@ fn main() -> ExitCode {
 }
 
 
-fn process_gradebook(
-    archive_path: &str,
-    out_directory: Option<&str>,
-    use_full_names: bool,
-) -> Result<Vec<Result<(), SubmissionError>>, GradebookError> {
-    // Read file into memory so that we can run through it multiple times
-    // --------------------------------------------------------------------------------------------
+/// One submitted file recorded in a `--manifest` run, passed back up from [`process_submission`] so that
+/// [`process_gradebook`] can build a cross-student digest index once every submission has been processed.
+struct ManifestEntry {
+    username: String,
+    original_name: String,
+    size: usize,
+    digest: String,
+}
 
-    println!("Loading zip file...");
-    let archive_data = fs::read(archive_path)?;
+/// Hex-encodes a SHA-256 digest of `bytes`, for `--manifest`'s per-file fingerprint.
+fn sha256_hex(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
 
-    // Open those bytes as a zip archive
-    // --------------------------------------------------------------------------------------------
+/// A `Write` wrapper that feeds every chunk through a SHA-256 hasher on its way to `inner`, so `--manifest` can
+/// fingerprint a plain submitted file as it's streamed to disk instead of buffering it twice.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    len: usize,
+}
 
-    let cursor = Cursor::new(archive_data);
-    let mut gradebook = ZipArchive::new(cursor)?;
+impl<W: Write> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, hasher: Sha256::new(), len: 0 }
+    }
 
+    /// Consumes the writer, returning the total number of bytes written and their hex-encoded SHA-256 digest.
+    fn finish(self) -> (usize, String) {
+        let digest = self.hasher.finalize();
+        (self.len, digest.iter().map(|byte| format!("{byte:02x}")).collect())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.len += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Parses every Blackboard submission datafile out of `gradebook` into a sorted `Vec<Submission>` (by username,
+/// then submission time) plus a count of how many attempts each student made. Shared by [`process_gradebook`] and
+/// [`list_gradebook`], which otherwise duplicate this exact scan-and-parse step.
+///
+/// `datafile_contents` is an out-parameter rather than part of the return value: each `Submission` borrows its
+/// string fields out of it, so it has to be owned by the caller and outlive the `Vec<Submission>` this returns.
+fn parse_submissions<'a>(
+    gradebook: &mut ZipArchive<impl Read + Seek>,
+    datafile_contents: &'a mut Vec<(String, String)>,
+) -> Result<(Vec<Submission<'a>>, HashMap<&'a str, u32>), GradebookError> {
     if gradebook.len() == 0 {
         return Err(GradebookError::Empty);
     }
 
-    // Get all of Blackboard's text files
-    // --------------------------------------------------------------------------------------------
-
     println!("Parsing submission data...");
 
     // We need to allocate and collect the filenames first because pulling the actual files out of the archive requires
@@ -201,7 +385,7 @@ fn process_gradebook(
     // Same goes for reading the text files all the way through: the `Submission` struct that holds the names of files
     // and stuff needs to hold string slices, which have to point somewhere. They point into the Strings owned by this
     // vector.
-    let datafile_contents = datafile_names
+    *datafile_contents = datafile_names
         .into_iter()
         .map(|filename| {
             // We can unwrap because we got this list of names from the archive itself, and we know no I/O problems can
@@ -220,7 +404,7 @@ fn process_gradebook(
     let mut submissions = datafile_contents
         .iter()
         .map(|(filename, contents)| {
-            let submission = Submission::new(&filename, contents);
+            let submission = Submission::new(filename, contents);
             // Count up the number of times this student submitted
             let count = attempt_counts.entry(submission.username).or_insert(0u32);
             *count += 1;
@@ -233,48 +417,176 @@ fn process_gradebook(
         unequal => unequal,
     });
 
+    Ok((submissions, attempt_counts))
+}
+
+fn process_gradebook(
+    archive_path: &str,
+    out_directory: Option<&str>,
+    use_full_names: bool,
+    password: Option<&[u8]>,
+    jobs: Option<usize>,
+    verify: bool,
+    manifest: bool,
+) -> Result<Vec<Result<Vec<SubmissionWarning>, SubmissionError>>, GradebookError> {
+    // Open the gradebook as a zip archive straight off disk, instead of reading the whole thing into memory: the
+    // central directory scan below and the `by_name` lookups it drives only ever need to seek around, never the
+    // entire file at once. Each parallel worker below reopens its own handle on `archive_path` the same way, so no
+    // `&mut` contention exists and peak memory no longer scales with the size of the gradebook export.
+    // --------------------------------------------------------------------------------------------
+
+    println!("Opening zip file...");
+    let file = File::open(archive_path)?;
+    let mut gradebook = ZipArchive::new(BufReader::new(file))?;
+
+    // Get all of Blackboard's text files
+    // --------------------------------------------------------------------------------------------
+
+    let mut datafile_contents = Vec::new();
+    let (mut submissions, attempt_counts) = parse_submissions(&mut gradebook, &mut datafile_contents)?;
+
+    // Assign each submission its attempt number up front, sequentially, so that the processing below can run each
+    // submission independently in parallel. They're sorted by date-time, so the 1st, 2nd, 3rd, etc submissions for
+    // each student are in order here.
+    let mut attempts_processed = HashMap::new();
+    for submission in &mut submissions {
+        let attempt_number = attempts_processed.entry(submission.username).or_insert(0u32);
+        *attempt_number += 1;
+        submission.attempt_number = *attempt_number;
+    }
 
     // Onto processing!
     // --------------------------------------------------------------------------------------------
 
-    println!("Processing submissions...");
+    // `num_threads(0)` tells rayon to pick a sensible default (the available parallelism) when `--jobs` wasn't given.
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs.unwrap_or(0))
+        .build()
+        .expect("failed to set up a thread pool");
+
+    println!("Processing submissions using {} thread(s)...", pool.current_num_threads());
 
     // Get the assignment name from the first one, they should always be the same. Use the parsed name instead of the
     // one from the gradebook filename, since that one is always garbled.
     let assn_name = submissions[0].assn_name;
     let assn_path = out_directory.unwrap_or(assn_name);
 
-    // For the students with multiple attempts, keep track of which ones we've seen so far so we know what number to
-    // give each attempt. They're sorted by date-time, so the 1st, 2nd, 3rd, etc submissions for each student should
-    // always be in order.
-    let mut attempts_processed = HashMap::new();
-    let mut results = Vec::with_capacity(submissions.len());
-    for submission in submissions {
-        // Start by determining what the folder for this submission should be called
-        let mut path = PathBuf::from(assn_path);
-        if use_full_names {
-            path.push(submission.fullname);
-        } else {
-            path.push(submission.username);
+    let results = pool.install(|| {
+        submissions
+            .into_par_iter()
+            .map(|submission| {
+                // Start by determining what the folder for this submission should be called
+                let mut path = PathBuf::from(assn_path);
+                if use_full_names {
+                    path.push(submission.fullname);
+                } else {
+                    path.push(submission.username);
+                }
+
+                // If they made more than one attempt, add an 'Attempt N' folder to the path.
+                let total_attempts = attempt_counts.get(submission.username).unwrap();
+                if *total_attempts > 1 {
+                    let digits = total_attempts.checked_ilog10().unwrap() as usize;
+                    path.push(format!("Attempt {:0>1$}", submission.attempt_number, digits));
+                }
+
+                println!("Processing student {} attempt #{}:", submission.fullname, submission.attempt_number);
+
+                // Each worker reopens its own handle on the gradebook file, so no `&mut` contention exists and
+                // nothing needs to keep the whole archive buffered in memory to share across threads.
+                let file = File::open(archive_path).expect("gradebook file should still be readable");
+                let reader = BufReader::new(file);
+                let mut gradebook =
+                    ZipArchive::new(reader).expect("gradebook bytes were already validated as a zip archive");
+
+                process_submission(&mut gradebook, submission, &path, password, verify, manifest)
+            })
+            .collect::<Vec<_>>()
+    });
+
+    // If `--manifest` was given, build a cross-student index keyed by digest and flag any digest shared by more
+    // than one student's username as a cheap first-pass copy-detection hint.
+    if manifest {
+        let mut by_digest: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        for (_, entries) in results.iter().filter_map(|result| result.as_ref().ok()) {
+            for entry in entries {
+                by_digest
+                    .entry(entry.digest.clone())
+                    .or_default()
+                    .push((entry.username.clone(), entry.original_name.clone()));
+            }
         }
 
-        // If they made more than one attempt, add an 'Attempt N' folder to the path.
-        let total_attempts = attempt_counts.get(submission.username).unwrap();
-        let attempt_number = attempts_processed.entry(submission.username).or_insert(0u32);
+        let duplicates = by_digest
+            .into_iter()
+            .filter(|(_, files)| files.iter().map(|(username, _)| username).collect::<HashSet<_>>().len() > 1)
+            .collect::<Vec<_>>();
+
+        if !duplicates.is_empty() {
+            println!("\nFound {} file(s) shared across more than one student:", duplicates.len());
+            for (digest, files) in duplicates {
+                println!("\tsha256:{digest}");
+                for (username, original_name) in files {
+                    println!("\t\t{username}: {original_name}");
+                }
+            }
+        }
+    }
+
+    Ok(results.into_iter().map(|result| result.map(|(warnings, _)| warnings)).collect())
+}
+
+
+/// Runs the same parsing as [`process_gradebook`], but instead of extracting anything, prints a summary of each
+/// student's submissions so a grader can sanity-check a gradebook export before writing anything to disk.
+fn list_gradebook(archive_path: &str) -> Result<(), GradebookError> {
+    // Open the gradebook straight off disk; see `process_gradebook` for why this doesn't read it into memory.
+    // --------------------------------------------------------------------------------------------
+
+    println!("Opening zip file...");
+    let file = File::open(archive_path)?;
+    let mut gradebook = ZipArchive::new(BufReader::new(file))?;
+
+    // Get all of Blackboard's text files
+    // --------------------------------------------------------------------------------------------
+
+    let mut datafile_contents = Vec::new();
+    let (submissions, attempt_counts) = parse_submissions(&mut gradebook, &mut datafile_contents)?;
+
+    // Print the summary
+    // --------------------------------------------------------------------------------------------
+
+    let assn_name = submissions[0].assn_name;
+    println!("\nAssignment: {assn_name}");
+    println!("{} submission(s) from {} student(s):", submissions.len(), attempt_counts.len());
+
+    let mut attempts_seen = HashMap::new();
+    for submission in &submissions {
+        let attempt_number = attempts_seen.entry(submission.username).or_insert(0u32);
         *attempt_number += 1;
 
-        if *total_attempts > 1 {
-            let digits = total_attempts.checked_ilog10().unwrap() as usize;
-            path.push(format!("Attempt {:0>1$}", attempt_number, digits));
+        if *attempt_number == 1 {
+            let total_attempts = attempt_counts.get(submission.username).unwrap();
+            println!("\n{} ({}) - {total_attempts} attempt(s)", submission.fullname, submission.username);
         }
 
-        println!("Processing student {} attempt #{}:", submission.fullname, attempt_number);
-        let sub_result = process_submission(&mut gradebook, submission, &path);
+        println!("\tAttempt {attempt_number}, submitted {}", submission.datetime);
+        println!("\t\tText submission: {}", if submission.text_submission.is_some() { "yes" } else { "no" });
+        println!("\t\tComments:        {}", if submission.comments.is_some() { "yes" } else { "no" });
 
-        results.push(sub_result);
+        if submission.files.is_empty() {
+            println!("\t\tFiles:           (none)");
+        } else {
+            println!("\t\tFiles:");
+            for SubmissionFile { original_name, archive_name } in &submission.files {
+                // `.size()` comes straight from the zip's central directory, so this doesn't decompress anything.
+                let size = gradebook.by_name(archive_name).map(|file| file.size()).unwrap_or(0);
+                println!("\t\t\t- {original_name} ({size} bytes)");
+            }
+        }
     }
 
-    Ok(results)
+    Ok(())
 }
 
 
@@ -282,11 +594,18 @@ fn process_submission(
     gradebook: &mut ZipArchive<impl Read + Seek>,
     submission: Submission,
     path: &Path,
-) -> Result<(), SubmissionError> {
+    password: Option<&[u8]>,
+    verify: bool,
+    manifest: bool,
+) -> Result<(Vec<SubmissionWarning>, Vec<ManifestEntry>), SubmissionError> {
     // Error handling
 
     let io_err = |e: io::Error| SubmissionError::from_io(&submission, e);
-    let zip_err = |e: ZipError| SubmissionError::from_zip(&submission, e);
+    let zip_err = |e: ZipError| SubmissionError::from_archive(&submission, e.into());
+    let archive_err = |e: ArchiveError| SubmissionError::from_archive(&submission, e);
+
+    let mut warnings = Vec::new();
+    let mut manifest_entries = Vec::new();
 
     // Make the output directory and create metadata files
     // --------------------------------------------------------------------------------------------
@@ -327,34 +646,129 @@ fn process_submission(
 
     for SubmissionFile { original_name, archive_name } in &submission.files {
         let mut file = gradebook.by_name(archive_name).map_err(zip_err)?;
-        let mut buff = Vec::new();
-        file.read_to_end(&mut buff).map_err(io_err)?;
-
-        // If they submitted any ZIP files, unzip them here.
         let output_name = Path::new(original_name);
-        if output_name.extension().is_some_and(|ext| ext == "zip") {
-            // Output directory is named after the zip file
-            let mut folder_name = OsString::from("[Unzipped] ");
-            folder_name.push(output_name.file_stem().unwrap());
+
+        // If they submitted a (possibly nested) archive, expand it here instead of writing it out flat. Expanding
+        // an archive needs random access into it (and, on a failed extraction, the raw bytes to fall back to), so
+        // unlike the plain-file case below, this is the one place that still buffers the whole file in memory.
+        if let Some(adapter) = archive::adapter_for(original_name) {
+            let mut buff = Vec::new();
+            if let Err(e) = file.read_to_end(&mut buff) {
+                // A submitted archive that's corrupt/truncated in the gradebook itself doesn't sink the whole
+                // submission, regardless of `--verify`: write out whatever we managed to read and warn instead.
+                let mut raw_path = path.to_owned();
+                raw_path.push(output_name);
+                println!("\tWriting   \t{} (corrupt/truncated, left as-is)", output_name.to_string_lossy());
+                fs::write(raw_path, &buff).map_err(io_err)?;
+
+                warnings.push(SubmissionWarning::corrupt_archive(&submission, original_name, e));
+                continue;
+            }
+
+            if manifest {
+                manifest_entries.push(ManifestEntry {
+                    username: submission.username.to_string(),
+                    original_name: original_name.to_string(),
+                    size: buff.len(),
+                    digest: sha256_hex(&buff),
+                });
+            }
+
+            // Zips carry a per-entry CRC-32, so validate the whole thing before extracting: this catches a
+            // truncated/corrupt submission up front instead of leaving a half-extracted folder on disk.
+            if archive::is_zip(original_name) {
+                match archive::verify_zip(&buff, password) {
+                    Ok(()) => (),
+                    Err(ArchiveError::Zip(e)) if archive::is_password_error(&e) => (), // handled below instead
+                    Err(e) => {
+                        let mut raw_path = path.to_owned();
+                        raw_path.push(output_name);
+                        println!("\tWriting   \t{} (corrupt archive, left as-is)", output_name.to_string_lossy());
+                        fs::write(raw_path, &buff).map_err(io_err)?;
+
+                        warnings.push(SubmissionWarning::corrupt_archive(&submission, original_name, e));
+                        continue;
+                    },
+                }
+            }
 
             let mut folder_path = path.to_owned();
-            folder_path.push(folder_name);
+            folder_path.push(archive::extracted_dir_name(output_name));
 
             println!("\tExtracting\t{}", output_name.to_string_lossy());
-            let cursor = Cursor::new(buff);
-            ZipArchive::new(cursor)
-                .map_err(zip_err)?
-                .extract(folder_path)
-                .map_err(zip_err)?;
+            let extracted =
+                archive::extract_recursive(adapter, &buff, &folder_path, password, archive::DEFAULT_MAX_DEPTH);
+
+            match extracted {
+                Ok(issues) => {
+                    for CorruptNested { name, detail } in issues.corrupt {
+                        warnings.push(SubmissionWarning::corrupt_archive(&submission, &name, detail));
+                    }
+                    for EncryptedNested { name, detail } in issues.encrypted {
+                        warnings.push(SubmissionWarning::encrypted_archive(&submission, &name, detail));
+                    }
+                },
+                // A locked zip shouldn't sink the whole submission: fall back to the raw bytes and warn instead.
+                Err(ArchiveError::Zip(e)) if archive::is_password_error(&e) => {
+                    let _ = fs::remove_dir_all(&folder_path);
+
+                    let mut raw_path = path.to_owned();
+                    raw_path.push(output_name);
+                    println!("\tWriting   \t{} (password-protected, left as-is)", output_name.to_string_lossy());
+                    fs::write(raw_path, &buff).map_err(io_err)?;
+
+                    warnings.push(SubmissionWarning::encrypted_archive(&submission, original_name, e));
+                },
+                Err(e) => return Err(archive_err(e)),
+            }
         } else {
-            let mut path = path.to_owned();
-            path.push(output_name);
+            // Plain files stream straight from the gradebook to disk via `io::copy`, which moves a few kilobytes
+            // at a time; when `--manifest` is on, `HashingWriter` fingerprints those same chunks on the way past,
+            // so nothing needs the whole file sitting in memory at once.
+            let mut out_path = path.to_owned();
+            out_path.push(output_name);
             println!("\tWriting   \t{}", output_name.to_string_lossy());
-            fs::write(path, &mut buff).map_err(io_err)?;
+
+            let out = File::create(&out_path).map_err(io_err)?;
+            let mut writer = HashingWriter::new(out);
+            let copied = io::copy(&mut file, &mut writer);
+            let (size, digest) = writer.finish();
+
+            match copied {
+                Ok(_) if manifest => {
+                    manifest_entries.push(ManifestEntry {
+                        username: submission.username.to_string(),
+                        original_name: original_name.to_string(),
+                        size,
+                        digest,
+                    });
+                },
+                Ok(_) => (),
+                // As above: with `--verify`, leave whatever got written and warn instead of aborting.
+                Err(e) if verify => {
+                    println!("\tWriting   \t{} (corrupt/truncated, left as-is)", output_name.to_string_lossy());
+                    warnings.push(SubmissionWarning::corrupt_archive(&submission, original_name, e));
+                },
+                Err(e) => return Err(io_err(e)),
+            }
         }
     }
 
-    Ok(())
+    if manifest {
+        let mut manifest_path = path.to_owned();
+        manifest_path.push("[Blackboard] Manifest.txt");
+        println!("\tWriting   \t[Blackboard] Manifest.txt");
+
+        let mut contents =
+            format!("Submission manifest for {} (attempt {})\n\n", submission.fullname, submission.datetime);
+        for entry in &manifest_entries {
+            contents.push_str(&format!("{}\t{} bytes\tsha256:{}\n", entry.original_name, entry.size, entry.digest));
+        }
+
+        fs::write(manifest_path, contents).map_err(io_err)?;
+    }
+
+    Ok((warnings, manifest_entries))
 }
 
 
@@ -368,6 +782,9 @@ struct Submission<'a> {
     pub text_submission: Option<&'a str>,
     pub comments: Option<&'a str>,
     pub files: Vec<SubmissionFile<'a>>,
+    /// Which attempt (1-indexed) this is out of all of this student's submissions. Computed up front, sequentially,
+    /// so that submissions can then be processed in parallel without needing to share any mutable counter.
+    pub attempt_number: u32,
 }
 
 impl<'a> Submission<'a> {
@@ -425,6 +842,7 @@ impl<'a> Submission<'a> {
             text_submission,
             comments,
             files,
+            attempt_number: 0, // filled in once all submissions are sorted, see `process_gradebook`
         }
     }
 }